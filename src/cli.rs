@@ -21,6 +21,7 @@ pub enum SubCommands {
     Init(KeyInit),
     Revoke(KeyRevoke),
     Renew(KeyRenew),
+    Cert(KeyCert),
 }
 
 /// Generate and add a new key to the user's authorized_keys on the remote host.
@@ -35,7 +36,8 @@ pub struct KeyInit {
     /// The target to generate a keypair to. This follows the form [username@]host.
     pub target: String,
 
-    /// Which type of key to use. RSA is well supported, while ed25519 is the most recent.
+    /// Which type of key to use, optionally with a size/curve, e.g. `rsa:4096`,
+    /// `ecdsa:384`. RSA is well supported, while ed25519 is the most recent.
     #[clap(short = "t", long = "type", default_value = "ed25519")]
     pub key_type: KeyType,
 
@@ -57,6 +59,40 @@ pub struct KeyInit {
     /// know what you're doing!
     #[clap(long = "overwrite-ssh-keys")]
     pub force: bool,
+
+    /// Sign the generated public key into an SSH certificate using this CA
+    /// private key. When omitted, no certificate is issued.
+    #[clap(long)]
+    pub ca_key: Option<String>,
+
+    /// Comma-separated list of principals (usernames or hostnames) the
+    /// certificate is valid for. Required when `--ca-key` is set.
+    #[clap(long)]
+    pub principals: Option<String>,
+
+    /// How long the issued certificate remains valid for, e.g. `+52w`.
+    #[clap(long, default_value = "+52w")]
+    pub validity: String,
+
+    /// An identifier embedded in the certificate, used for logging on the
+    /// remote host. Defaults to the target host.
+    #[clap(long)]
+    pub cert_id: Option<String>,
+
+    /// Store the host's known_hosts entry in OpenSSH's hashed form, rather
+    /// than as a plaintext hostname.
+    #[clap(long)]
+    pub hash_known_hosts: bool,
+
+    /// Add the newly generated private key to a running ssh-agent, so it's
+    /// usable immediately without re-typing the passphrase.
+    #[clap(long)]
+    pub add_to_agent: bool,
+
+    /// How long the key should stay loaded in the agent, e.g. `1h`. Only
+    /// meaningful together with `--add-to-agent`.
+    #[clap(long)]
+    pub agent_lifetime: Option<String>,
 }
 
 impl From<KeyRenew> for KeyInit {
@@ -68,6 +104,13 @@ impl From<KeyRenew> for KeyInit {
             port: key_renew.port,
             passphrase: key_renew.password,
             force: key_renew.force,
+            ca_key: key_renew.ca_key,
+            principals: key_renew.principals,
+            validity: key_renew.validity,
+            cert_id: key_renew.cert_id,
+            hash_known_hosts: key_renew.hash_known_hosts,
+            add_to_agent: key_renew.add_to_agent,
+            agent_lifetime: key_renew.agent_lifetime,
         }
     }
 }
@@ -113,7 +156,8 @@ pub struct KeyRenew {
     /// The target to generate a keypair to. This follows the form [username@]host.
     pub target: String,
 
-    /// Which type of key to use. RSA is well supported, while ed25519 is the most recent.
+    /// Which type of key to use, optionally with a size/curve, e.g. `rsa:4096`,
+    /// `ecdsa:384`. RSA is well supported, while ed25519 is the most recent.
     #[clap(short = "t", long = "type", default_value = "ed25519")]
     pub key_type: KeyType,
 
@@ -144,32 +188,129 @@ pub struct KeyRenew {
     /// pre-emptive safety measure.
     #[clap(long)]
     pub delete_identity_file: bool,
+
+    /// Sign the generated public key into an SSH certificate using this CA
+    /// private key. When omitted, no certificate is issued.
+    #[clap(long)]
+    pub ca_key: Option<String>,
+
+    /// Comma-separated list of principals (usernames or hostnames) the
+    /// certificate is valid for. Required when `--ca-key` is set.
+    #[clap(long)]
+    pub principals: Option<String>,
+
+    /// How long the issued certificate remains valid for, e.g. `+52w`.
+    #[clap(long, default_value = "+52w")]
+    pub validity: String,
+
+    /// An identifier embedded in the certificate, used for logging on the
+    /// remote host. Defaults to the target host.
+    #[clap(long)]
+    pub cert_id: Option<String>,
+
+    /// Store the host's known_hosts entry in OpenSSH's hashed form, rather
+    /// than as a plaintext hostname.
+    #[clap(long)]
+    pub hash_known_hosts: bool,
+
+    /// Add the newly generated private key to a running ssh-agent, so it's
+    /// usable immediately without re-typing the passphrase.
+    #[clap(long)]
+    pub add_to_agent: bool,
+
+    /// How long the key should stay loaded in the agent, e.g. `1h`. Only
+    /// meaningful together with `--add-to-agent`.
+    #[clap(long)]
+    pub agent_lifetime: Option<String>,
+}
+
+/// Sign an existing public key into an SSH certificate using a CA key.
+///
+/// This is useful when a keypair was generated separately from `init` (or
+/// needs to be re-signed with a new validity window) without touching the
+/// underlying keypair.
+#[derive(Clap, Clone)]
+pub struct KeyCert {
+    /// The name of the public key file to sign, without the .pub file
+    /// extension.
+    pub identity_file_path: String,
+
+    /// The CA private key used to sign the certificate.
+    #[clap(long)]
+    pub ca_key: String,
+
+    /// Comma-separated list of principals (usernames or hostnames) the
+    /// certificate is valid for.
+    #[clap(long)]
+    pub principals: String,
+
+    /// How long the issued certificate remains valid for, e.g. `+52w`.
+    #[clap(long, default_value = "+52w")]
+    pub validity: String,
+
+    /// An identifier embedded in the certificate, used for logging on the
+    /// remote host. Defaults to the identity file name.
+    #[clap(long)]
+    pub cert_id: Option<String>,
+
+    /// Overwrite an existing certificate file with the same name.
+    #[clap(long = "overwrite-ssh-keys")]
+    pub force: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
     UnknownKeyType(String),
+    InvalidKeySize(String),
 }
 
+/// A key type paired with its strength: RSA bit length, ECDSA curve size,
+/// or, for key types without a variable size, a fixed default.
 #[derive(Debug, Clone)]
-pub struct KeyType(pub OsshKeyType);
+pub struct KeyType(pub OsshKeyType, pub usize);
 
 impl std::str::FromStr for KeyType {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "rsa" => Ok(KeyType(OsshKeyType::RSA)),
-            "dsa" => Ok(KeyType(OsshKeyType::DSA)),
-            "ed25519" => Ok(KeyType(OsshKeyType::ED25519)),
-            "ecdsa" => Ok(KeyType(OsshKeyType::ECDSA)),
-            _ => Err(ParseError::UnknownKeyType(s.to_string())),
+        let mut parts = s.splitn(2, ':');
+        let kind = parts.next().unwrap();
+        let size = parts.next();
+
+        let (osshkeytype, default_size) = match kind {
+            "rsa" => (OsshKeyType::RSA, 3072),
+            "dsa" => (OsshKeyType::DSA, 1024),
+            "ed25519" => (OsshKeyType::ED25519, 256),
+            "ecdsa" => (OsshKeyType::ECDSA, 256),
+            _ => return Err(ParseError::UnknownKeyType(s.to_string())),
+        };
+
+        let size = match size {
+            None => default_size,
+            Some(size) => size
+                .parse::<usize>()
+                .map_err(|_| ParseError::InvalidKeySize(s.to_string()))?,
+        };
+
+        match osshkeytype {
+            OsshKeyType::RSA if size < 1024 => {
+                return Err(ParseError::InvalidKeySize(s.to_string()))
+            }
+            OsshKeyType::ECDSA if ![256, 384, 521].contains(&size) => {
+                return Err(ParseError::InvalidKeySize(s.to_string()))
+            }
+            OsshKeyType::DSA | OsshKeyType::ED25519 if size != default_size => {
+                return Err(ParseError::InvalidKeySize(s.to_string()))
+            }
+            _ => {}
         }
+
+        Ok(KeyType(osshkeytype, size))
     }
 }
 
 impl Default for KeyType {
     fn default() -> Self {
-        Self(OsshKeyType::ED25519)
+        Self(OsshKeyType::ED25519, 256)
     }
 }
 
@@ -179,6 +320,9 @@ impl std::string::ToString for ParseError {
             Self::UnknownKeyType(_) => {
                 String::from(format!("Must be one of rsa, dsa, ecdsa, or ed25519"))
             }
+            Self::InvalidKeySize(s) => {
+                format!("Invalid key size for `{}`. RSA requires >= 1024 bits, ECDSA requires one of 256, 384, or 521, DSA is fixed at 1024 and ed25519 at 256", s)
+            }
         }
     }
 }
@@ -1,14 +1,22 @@
 use clap::derive::Clap;
-use cli::{KeyInit, KeyRevoke, Opts, SubCommands};
+use cli::{KeyCert, KeyInit, KeyRevoke, Opts, SubCommands};
 use osshkeys::{cipher::Cipher, KeyPair};
 use std::fs::{read_to_string, remove_file, OpenOptions};
-use std::{io::Write, path::Path, process::Command};
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
 mod cli;
 
 #[derive(Debug)]
 enum SshKeyCtlError {
     OSshKeySerialize(osshkeys::error::Error),
     IO(std::io::Error),
+    SshKeygenFailed,
+    MalformedPublicKey,
+    SshAddFailed,
+    RemoteCommandFailed,
 }
 
 impl From<osshkeys::error::Error> for SshKeyCtlError {
@@ -30,17 +38,23 @@ fn main() -> Result<(), SshKeyCtlError> {
             revoke(&args.clone().into())?;
             init(&args.into())?;
         }
+        SubCommands::Cert(args) => cert(&args)?,
     }
     Ok(())
 }
 
 fn init(args: &KeyInit) -> Result<(), SshKeyCtlError> {
-    let mut key_pair = KeyPair::generate(args.key_type.0, 0)?;
+    let mut key_pair = KeyPair::generate(args.key_type.0, args.key_type.1)?;
     *key_pair.comment_mut() = args.comment.clone().unwrap();
     let mut ssh_folder = dirs::home_dir().unwrap();
     ssh_folder.push(".ssh");
 
     let target = args.target.split("@").collect::<Vec<_>>();
+    let user = match target.as_slice() {
+        [_] => None,
+        [user, _] => Some(*user),
+        _ => panic!(":("),
+    };
     let target = match target.as_slice() {
         [target] => target,
         [_, target] => target,
@@ -68,7 +82,401 @@ fn init(args: &KeyInit) -> Result<(), SshKeyCtlError> {
     pub_key_data.push('\n' as u8);
     safely_write(pub_key_path.as_path(), &pub_key_data, false, args.force)?;
 
-    // todo: edit .ssh/config file
+    if let Some(ca_key) = &args.ca_key {
+        let principals = args
+            .principals
+            .as_ref()
+            .expect("--principals is required when --ca-key is set");
+        let cert_id = args.cert_id.clone().unwrap_or_else(|| target.to_string());
+        sign_certificate(
+            pub_key_path.as_path(),
+            ca_key,
+            &cert_id,
+            principals,
+            &args.validity,
+            args.force,
+        )?;
+    }
+
+    pin_host_key(target, args.port, args.force, args.hash_known_hosts)?;
+
+    if args.add_to_agent {
+        add_to_agent(priv_key_path.as_path(), args.agent_lifetime.as_deref())?;
+    }
+
+    update_ssh_config_host(
+        target,
+        target,
+        args.port,
+        user,
+        priv_key_path.as_path(),
+        args.force,
+    )?;
+
+    Ok(())
+}
+
+/// Loads a private key into a running ssh-agent, so a freshly generated
+/// per-host key is usable immediately without re-typing its passphrase.
+/// Relies on `$SSH_AUTH_SOCK` already pointing at an agent, same as the
+/// `ssh-add` binary it shells out to.
+fn add_to_agent(priv_key_path: &Path, lifetime: Option<&str>) -> Result<(), SshKeyCtlError> {
+    let mut command = Command::new("ssh-add");
+    if let Some(lifetime) = lifetime {
+        command.args(&["-t", lifetime]);
+    }
+    let status = command.arg(priv_key_path).status()?;
+
+    if !status.success() {
+        return Err(SshKeyCtlError::SshAddFailed);
+    }
+    Ok(())
+}
+
+/// Removes a private key's identity from a running ssh-agent, keeping the
+/// agent's loaded keys consistent with what's left on disk after a revoke.
+fn remove_from_agent(priv_key_path: &Path) -> Result<(), SshKeyCtlError> {
+    let status = Command::new("ssh-add")
+        .arg("-d")
+        .arg(priv_key_path)
+        .status()?;
+
+    if !status.success() {
+        return Err(SshKeyCtlError::SshAddFailed);
+    }
+    Ok(())
+}
+
+/// Records the remote host's identity in `known_hosts` so that the first
+/// real connection isn't blind trust-on-first-use, mirroring the
+/// scan-then-pin workflow other fleet tools use for host key management. If
+/// an entry for the host already exists with a different key of the same
+/// type, refuses unless `force` is set; entries of key types the scan did
+/// not return are left in place.
+fn pin_host_key(
+    host: &str,
+    port: u16,
+    force: bool,
+    hash_known_hosts: bool,
+) -> Result<(), SshKeyCtlError> {
+    let scan = Command::new("ssh-keyscan")
+        .args(&["-p", &port.to_string(), host])
+        .output()?;
+    if !scan.status.success() {
+        return Err(SshKeyCtlError::RemoteCommandFailed);
+    }
+    let scanned = String::from_utf8_lossy(&scan.stdout);
+    if scanned
+        .lines()
+        .all(|line| line.trim().is_empty() || line.trim().starts_with('#'))
+    {
+        return Err(SshKeyCtlError::RemoteCommandFailed);
+    }
+
+    let mut known_hosts_path = dirs::home_dir().unwrap();
+    known_hosts_path.push(".ssh");
+    known_hosts_path.push("known_hosts");
+
+    let existing = read_to_string(&known_hosts_path).unwrap_or_default();
+    let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+
+    let canonical_host = if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    };
+
+    for scan_line in scanned.lines() {
+        let scan_line = scan_line.trim();
+        if scan_line.is_empty() || scan_line.starts_with('#') {
+            continue;
+        }
+        let key_part = scan_line
+            .splitn(2, char::is_whitespace)
+            .nth(1)
+            .ok_or(SshKeyCtlError::MalformedPublicKey)?;
+        let key_type = key_part
+            .split_whitespace()
+            .next()
+            .ok_or(SshKeyCtlError::MalformedPublicKey)?;
+
+        if let Some((index, existing_key)) = find_known_host_key(&lines, &canonical_host, key_type)
+        {
+            if existing_key == key_part {
+                continue;
+            }
+            if !force {
+                panic!(
+                    "known_hosts entry for host already exists with a different {} key",
+                    key_type
+                );
+            }
+            lines.remove(index);
+        }
+
+        let host_field = if hash_known_hosts {
+            hash_known_hosts_entry(&canonical_host)
+        } else {
+            canonical_host.clone()
+        };
+        lines.push(format!("{} {}", host_field, key_part));
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    atomic_write(&known_hosts_path, contents.as_bytes())
+}
+
+/// Finds the `known_hosts` line (plaintext or hashed) matching `host`,
+/// returning its index and key field.
+fn find_known_host_key(lines: &[String], host: &str, key_type: &str) -> Option<(usize, String)> {
+    lines.iter().enumerate().find_map(|(i, line)| {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+        let mut fields = trimmed.splitn(2, char::is_whitespace);
+        let host_field = fields.next()?;
+        let key_field = fields.next()?;
+        if key_field.split_whitespace().next() != Some(key_type) {
+            return None;
+        }
+        let matches = if host_field.starts_with("|1|") {
+            hashed_host_matches(host_field, host)
+        } else {
+            host_field.split(',').any(|h| h == host)
+        };
+        matches.then(|| (i, key_field.to_string()))
+    })
+}
+
+/// Checks whether a hashed known_hosts host field (`|1|<salt>|<hmac>`) was
+/// produced from `host`, by recomputing the HMAC-SHA1 with the stored salt.
+fn hashed_host_matches(entry: &str, host: &str) -> bool {
+    let mut parts = entry.split('|').skip(2);
+    let salt = match parts.next().map(base64::decode) {
+        Some(Ok(salt)) => salt,
+        _ => return false,
+    };
+    let expected = match parts.next().map(base64::decode) {
+        Some(Ok(expected)) => expected,
+        _ => return false,
+    };
+    hmac_sha1(&salt, host.as_bytes()) == expected
+}
+
+/// Hashes a hostname into OpenSSH's `|1|<salt>|<hmac>` known_hosts form
+/// using a fresh random salt.
+fn hash_known_hosts_entry(host: &str) -> String {
+    use rand::RngCore;
+    let mut salt = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let hmac = hmac_sha1(&salt, host.as_bytes());
+    format!("|1|{}|{}", base64::encode(&salt), base64::encode(&hmac))
+}
+
+fn hmac_sha1(key: &[u8], data: &[u8]) -> Vec<u8> {
+    use hmac::{Hmac, Mac};
+    use sha1::Sha1;
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts a key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Atomically rewrites a file's full contents via a temp file and rename, so
+/// readers never observe a partially-written file.
+fn atomic_write(path: &Path, buffer: &[u8]) -> Result<(), SshKeyCtlError> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut tmp_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        tmp_file.write_all(buffer)?;
+    }
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Inserts or updates a `Host <target>` stanza in `~/.ssh/config` so that
+/// the per-host key generated by `init` is actually selected without the
+/// user having to pass `-i` on every connection. Refuses to touch an
+/// existing stanza for `target` unless `force` is set.
+fn update_ssh_config_host(
+    target: &str,
+    host: &str,
+    port: u16,
+    user: Option<&str>,
+    identity_file: &Path,
+    force: bool,
+) -> Result<(), SshKeyCtlError> {
+    let mut config_path = dirs::home_dir().unwrap();
+    config_path.push(".ssh");
+    config_path.push("config");
+
+    let existing = read_to_string(&config_path).unwrap_or_default();
+    let mut blocks = split_config_blocks(&existing);
+    let stanza = render_host_stanza(target, host, port, user, identity_file);
+
+    match blocks
+        .iter()
+        .position(|block| block_matches_host(block, target))
+    {
+        Some(_) if !force => {
+            panic!(
+                "a Host entry for `{}` already exists in .ssh/config",
+                target
+            );
+        }
+        Some(index) => blocks[index] = stanza,
+        None => blocks.push(stanza),
+    }
+
+    atomic_write(&config_path, render_config_blocks(&blocks).as_bytes())
+}
+
+/// Removes the `Host <target>` stanza from `~/.ssh/config`, mirroring the
+/// identity file cleanup done by `revoke --delete-identity-file`.
+fn remove_ssh_config_host(target: &str) -> Result<(), SshKeyCtlError> {
+    let mut config_path = dirs::home_dir().unwrap();
+    config_path.push(".ssh");
+    config_path.push("config");
+
+    let existing = match read_to_string(&config_path) {
+        Ok(existing) => existing,
+        Err(_) => return Ok(()),
+    };
+    let blocks = split_config_blocks(&existing);
+    let blocks = blocks
+        .into_iter()
+        .filter(|block| !block_matches_host(block, target))
+        .collect::<Vec<_>>();
+
+    atomic_write(&config_path, render_config_blocks(&blocks).as_bytes())
+}
+
+/// Splits an `.ssh/config` file into blocks, each starting at a `Host` line
+/// (anything before the first `Host` line is kept as its own leading
+/// block).
+fn split_config_blocks(config: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+
+    for line in config.lines() {
+        if is_host_line(line) && !current.trim().is_empty() {
+            blocks.push(current.trim_end().to_string());
+            current = String::new();
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        blocks.push(current.trim_end().to_string());
+    }
+
+    blocks
+}
+
+fn render_config_blocks(blocks: &[String]) -> String {
+    let mut contents = blocks.join("\n\n");
+    contents.push('\n');
+    contents
+}
+
+fn is_host_line(line: &str) -> bool {
+    line.trim()
+        .split_whitespace()
+        .next()
+        .map(|word| word.eq_ignore_ascii_case("host"))
+        .unwrap_or(false)
+}
+
+/// Whether a config block's `Host` line lists `target` among its patterns.
+fn block_matches_host(block: &str, target: &str) -> bool {
+    block
+        .lines()
+        .next()
+        .filter(|line| is_host_line(line))
+        .map(|line| {
+            line.split_whitespace()
+                .skip(1)
+                .any(|pattern| pattern == target)
+        })
+        .unwrap_or(false)
+}
+
+fn render_host_stanza(
+    target: &str,
+    host: &str,
+    port: u16,
+    user: Option<&str>,
+    identity_file: &Path,
+) -> String {
+    let mut lines = vec![format!("Host {}", target), format!("    HostName {}", host)];
+    lines.push(format!("    Port {}", port));
+    if let Some(user) = user {
+        lines.push(format!("    User {}", user));
+    }
+    lines.push(format!("    IdentityFile {}", identity_file.display()));
+    lines.push("    IdentitiesOnly yes".to_string());
+    lines.join("\n")
+}
+
+fn cert(args: &KeyCert) -> Result<(), SshKeyCtlError> {
+    let mut pub_key_path = dirs::home_dir().unwrap();
+    pub_key_path.push(".ssh");
+    pub_key_path.push(format!("{}.pub", args.identity_file_path));
+
+    let cert_id = args
+        .cert_id
+        .clone()
+        .unwrap_or_else(|| args.identity_file_path.clone());
+    sign_certificate(
+        pub_key_path.as_path(),
+        &args.ca_key,
+        &cert_id,
+        &args.principals,
+        &args.validity,
+        args.force,
+    )
+}
+
+/// Signs a public key into an OpenSSH certificate using `ssh-keygen`,
+/// mirroring the per-host/per-user certificate issuance workflow of other
+/// fleet management tools. The resulting `*-cert.pub` is placed next to the
+/// public key.
+fn sign_certificate(
+    pub_key_path: &Path,
+    ca_key: &str,
+    cert_id: &str,
+    principals: &str,
+    validity: &str,
+    force: bool,
+) -> Result<(), SshKeyCtlError> {
+    let mut cert_path = pub_key_path.to_path_buf();
+    cert_path.set_extension("");
+    let cert_file_name = format!(
+        "{}-cert.pub",
+        cert_path.file_name().unwrap().to_string_lossy()
+    );
+    cert_path.set_file_name(&cert_file_name);
+
+    if !force && cert_path.exists() {
+        panic!("file already exists");
+    }
+
+    let status = Command::new("ssh-keygen")
+        .args(&[
+            "-s", ca_key, "-I", cert_id, "-n", principals, "-V", validity,
+        ])
+        .arg(pub_key_path)
+        .status()?;
+
+    if !status.success() {
+        return Err(SshKeyCtlError::SshKeygenFailed);
+    }
+
     Ok(())
 }
 
@@ -124,21 +532,83 @@ fn revoke(args: &KeyRevoke) -> Result<(), SshKeyCtlError> {
     };
     key_file_path.push(format!("{}.pub", key_file_name));
     let key_data = read_to_string(&key_file_path)?;
-    let key_data = key_data.trim().replace("/", "\\/");
-    Command::new("ssh")
+    let local_blob =
+        parse_authorized_key_blob(&key_data).ok_or(SshKeyCtlError::MalformedPublicKey)?;
+
+    let remote_keys = Command::new("ssh")
+        .args(&[target, "-C", "cat .ssh/authorized_keys"])
+        .output()?;
+    if !remote_keys.status.success() {
+        return Err(SshKeyCtlError::RemoteCommandFailed);
+    }
+    let remote_keys = String::from_utf8_lossy(&remote_keys.stdout);
+
+    let remaining_keys = remote_keys
+        .lines()
+        .filter(|line| parse_authorized_key_blob(line).as_ref() != Some(&local_blob))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Preserve authorized_keys' existing permissions across the swap: the
+    // remote shell's umask would otherwise decide the tmp file's mode, which
+    // can leave it group/world-writable and trip OpenSSH's StrictModes.
+    let mut rewrite = Command::new("ssh")
         .args(&[
             target,
             "-C",
-            // todo: make gnu sed independent
-            &format!("sed -i '/{}/d' .ssh/authorized_keys", key_data),
+            "cat > .ssh/authorized_keys.tmp && \
+             chmod $(stat -c %a .ssh/authorized_keys 2>/dev/null || stat -f %OLp .ssh/authorized_keys) .ssh/authorized_keys.tmp && \
+             mv .ssh/authorized_keys.tmp .ssh/authorized_keys",
         ])
-        .spawn()?
-        .wait()?;
+        .stdin(Stdio::piped())
+        .spawn()?;
+    rewrite
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("{}\n", remaining_keys).as_bytes())?;
+    if !rewrite.wait()?.success() {
+        return Err(SshKeyCtlError::RemoteCommandFailed);
+    }
 
     if args.delete_identity_file {
         remove_file(&key_file_path)?;
         key_file_path.set_extension("");
+        // Best-effort: no agent running, or the key was never loaded into
+        // one, shouldn't block the rest of cleanup.
+        let _ = remove_from_agent(&key_file_path);
         remove_file(&key_file_path)?;
+        remove_ssh_config_host(target)?;
     }
     Ok(())
 }
+
+/// Known OpenSSH public key type identifiers, used to locate the key-type
+/// field of an `authorized_keys` line regardless of any leading options.
+const KNOWN_KEY_TYPES: &[&str] = &[
+    "ssh-rsa",
+    "ssh-dss",
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+];
+
+/// Parses a single `authorized_keys`-style line (`[options] <keytype>
+/// <base64-blob> [comment]`) and returns the decoded wire-format key blob, so
+/// that two keys can be compared regardless of differing options or
+/// comments. Returns `None` for blank lines, comments, or lines that don't
+/// carry a recognized key type.
+fn parse_authorized_key_blob(line: &str) -> Option<Vec<u8>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let keytype_index = line
+        .split_whitespace()
+        .position(|field| KNOWN_KEY_TYPES.contains(&field))?;
+    let blob = fields.nth(keytype_index + 1)?;
+    base64::decode(blob).ok()
+}